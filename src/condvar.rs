@@ -0,0 +1,100 @@
+use std::sync::{Arc, Condvar as StdCondvar, LockResult, Mutex as StdMutex, PoisonError};
+use std::time::Duration;
+
+use crate::lock_manager::LockManager;
+use crate::mutex::MutexGuard;
+
+/// An instrumented version of `std::sync::Condvar` that works with this
+/// crate's [`Mutex`].
+///
+/// A wait that is never notified is a classic liveness bug, so a parked
+/// thread is reported to the `LockManager` the same way a thread blocked
+/// on a lock is: if it is still holding other locks that another thread
+/// needs, that thread's wait-for search walks straight through the park
+/// and is flagged as deadlocked/livelocked.
+///
+/// The actual blocking is delegated to a private `std::sync::Condvar`
+/// guarded by its own gate mutex, since this type only needs to add
+/// instrumentation around the standard wait/notify dance, not reimplement
+/// thread parking.
+pub struct Condvar {
+    key: usize,
+    manager: Arc<LockManager>,
+    inner: StdCondvar,
+    gate: StdMutex<()>,
+}
+
+impl Condvar {
+    pub fn new() -> Self {
+        Self::with_manager(LockManager::get_global_manager())
+    }
+
+    /// Creates a condvar registered with `manager` instead of the global
+    /// one. Use this alongside a [`Mutex::with_manager`](crate::Mutex::with_manager)
+    /// on the same `manager`, so a park on this condvar is visible to that
+    /// manager's analysis instead of silently landing on the global one.
+    pub fn with_manager(manager: Arc<LockManager>) -> Self {
+        let key = manager.create_condvar();
+        Condvar {
+            key,
+            manager,
+            inner: StdCondvar::new(),
+            gate: StdMutex::new(()),
+        }
+    }
+
+    /// Atomically releases `guard`'s mutex and blocks until notified,
+    /// then reacquires it before returning.
+    pub fn wait<'l, T>(&self, guard: MutexGuard<'l, T>) -> LockResult<MutexGuard<'l, T>> {
+        let mutex = guard.mutex();
+        let gate = self.gate.lock().unwrap();
+        drop(guard);
+
+        self.manager.park(self.key);
+        let gate = self.inner.wait(gate).unwrap();
+        self.manager.unpark(self.key);
+        drop(gate);
+
+        mutex.lock()
+    }
+
+    /// Like [`Condvar::wait`], but gives up after `duration` even without
+    /// a notification. The returned `bool` is `true` if the wait timed out.
+    pub fn wait_timeout<'l, T>(
+        &self,
+        guard: MutexGuard<'l, T>,
+        duration: Duration,
+    ) -> LockResult<(MutexGuard<'l, T>, bool)> {
+        let mutex = guard.mutex();
+        let gate = self.gate.lock().unwrap();
+        drop(guard);
+
+        self.manager.park(self.key);
+        let (gate, result) = self.inner.wait_timeout(gate, duration).unwrap();
+        self.manager.unpark(self.key);
+        drop(gate);
+
+        match mutex.lock() {
+            Ok(reacquired) => Ok((reacquired, result.timed_out())),
+            Err(poisoned) => Err(PoisonError::new((poisoned.into_inner(), result.timed_out()))),
+        }
+    }
+
+    /// Wakes one thread blocked in [`Condvar::wait`], if any.
+    pub fn notify_one(&self) {
+        let _gate = self.gate.lock().unwrap();
+        self.inner.notify_one();
+    }
+
+    /// Wakes every thread blocked in [`Condvar::wait`].
+    pub fn notify_all(&self) {
+        let _gate = self.gate.lock().unwrap();
+        self.inner.notify_all();
+    }
+}
+
+impl Default for Condvar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
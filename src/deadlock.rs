@@ -0,0 +1,28 @@
+use std::thread::ThreadId;
+
+#[cfg(feature = "backtrace")]
+use std::{backtrace::Backtrace, sync::Arc};
+
+/// One link in a detected deadlock cycle: a thread, the lock it was waiting
+/// on, and (with the `backtrace` feature enabled) where the thread that
+/// currently holds that lock acquired it.
+#[derive(Clone)]
+pub struct DeadlockParticipant {
+    pub thread: ThreadId,
+    pub lock: usize,
+    #[cfg(feature = "backtrace")]
+    pub acquired_at: Option<Arc<Backtrace>>,
+}
+
+impl std::fmt::Debug for DeadlockParticipant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DeadlockParticipant")
+            .field("thread", &self.thread)
+            .field("lock", &self.lock)
+            .finish()
+    }
+}
+
+/// Invoked with the ordered cycle of threads/locks that form a deadlock,
+/// instead of the default behaviour of panicking.
+pub(crate) type DeadlockCallback = std::sync::Arc<dyn Fn(&[DeadlockParticipant]) + Send + Sync>;
@@ -0,0 +1,17 @@
+//! `deadlocks_debugger` provides drop-in replacements for the primitives in
+//! `std::sync` that are instrumented to track lock acquisition across an
+//! application and help surface deadlocks.
+
+mod condvar;
+mod deadlock;
+mod lock_manager;
+mod mcs;
+mod mutex;
+mod poison;
+mod rwlock;
+
+pub use condvar::Condvar;
+pub use deadlock::DeadlockParticipant;
+pub use lock_manager::LockManager;
+pub use mutex::{Mutex, MutexGuard};
+pub use rwlock::{RwLock, RwLockReadGuard, RwLockWriteGuard};
@@ -0,0 +1,331 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, OnceLock, RwLock, RwLockWriteGuard};
+use std::thread::ThreadId;
+
+use crate::deadlock::{DeadlockCallback, DeadlockParticipant};
+
+/// Registry of instrumented locks. The global manager, from
+/// [`LockManager::get_global_manager`], tracks every lock created with a
+/// default constructor; a manager from [`LockManager::new`] tracks only the
+/// locks created with it.
+///
+/// Each instrumented lock (`Mutex`, `RwLock`, ...) registers itself with a
+/// `LockManager` on creation and reports acquisition/release through it, so
+/// that the manager can see who holds what, who is waiting on whom, and
+/// detect deadlocks across the whole graph.
+pub struct LockManager {
+    state: RwLock<ManagerState>,
+}
+
+pub(crate) struct ManagerState {
+    next_key: usize,
+    pub(crate) locks: HashMap<usize, LockRepresentation>,
+    /// The lock key each thread is currently blocked trying to acquire.
+    waiting_on: HashMap<ThreadId, usize>,
+    callback: Option<DeadlockCallback>,
+    /// Every cycle detected so far, in detection order, kept around so
+    /// callers can assert on it directly instead of only observing it
+    /// through [`LockManager::on_deadlock`].
+    detected: Vec<Vec<DeadlockParticipant>>,
+}
+
+impl LockManager {
+    /// Creates a fresh, empty manager, independent of the global one.
+    ///
+    /// Useful for tests or subsystems that want to analyse their own set
+    /// of locks in isolation, rather than sharing the process-wide graph
+    /// every lock created with a default constructor registers with.
+    pub fn new() -> Self {
+        LockManager {
+            state: RwLock::new(ManagerState {
+                next_key: 0,
+                locks: HashMap::new(),
+                waiting_on: HashMap::new(),
+                callback: None,
+                detected: Vec::new(),
+            }),
+        }
+    }
+
+    /// Returns the process-wide manager shared by every lock created with
+    /// its default constructor.
+    pub fn get_global_manager() -> Arc<LockManager> {
+        static GLOBAL: OnceLock<Arc<LockManager>> = OnceLock::new();
+        GLOBAL.get_or_init(|| Arc::new(LockManager::new())).clone()
+    }
+
+    /// Registers a new lock and returns the key it is tracked under.
+    pub(crate) fn create_lock(&self) -> usize {
+        let mut state = self.write_lock();
+        let key = state.next_key;
+        state.next_key += 1;
+        state.locks.insert(key, LockRepresentation::new());
+        key
+    }
+
+    /// Registers a new `Condvar`'s own key and returns it. Unlike a real
+    /// lock, nothing ever holds this key; see [`LockManager::park`].
+    pub(crate) fn create_condvar(&self) -> usize {
+        let mut state = self.write_lock();
+        let key = state.next_key;
+        state.next_key += 1;
+        state.locks.insert(key, LockRepresentation::new_condvar());
+        key
+    }
+
+    pub(crate) fn write_lock(&self) -> RwLockWriteGuard<'_, ManagerState> {
+        self.state.write().unwrap()
+    }
+
+    /// Registers the current thread as blocked on `lock`, and checks
+    /// whether doing so closes a cycle in the wait-for graph. If it does,
+    /// reports the deadlock immediately instead of letting the caller keep
+    /// spinning.
+    pub(crate) fn wait_for(&self, lock: usize) {
+        let thread = std::thread::current().id();
+        let cycle = self.write_lock().begin_wait(thread, lock);
+        if let Some(cycle) = cycle {
+            self.report_deadlock(cycle);
+        }
+    }
+
+    /// Clears the calling thread's wait-for edge once it stops blocking,
+    /// either because it acquired the lock or because it woke from a
+    /// `Condvar` park.
+    pub(crate) fn stop_waiting(&self) {
+        let thread = std::thread::current().id();
+        self.write_lock().waiting_on.remove(&thread);
+    }
+
+    /// Registers the current thread as parked on `lock` (a `Condvar`'s own
+    /// key). Unlike a real lock, nobody ever holds a condvar key, so a
+    /// waiter can only ever become runnable again via a notification this
+    /// analysis cannot observe in advance; `walk` treats reaching one as
+    /// an immediate dead end, so a thread depending (possibly transitively)
+    /// on a parked thread's *other* locks is reported right away instead
+    /// of waiting for a cycle that may never close.
+    pub(crate) fn park(&self, lock: usize) {
+        self.wait_for(lock);
+    }
+
+    /// Undoes [`LockManager::park`] once the current thread wakes up.
+    pub(crate) fn unpark(&self, _lock: usize) {
+        self.stop_waiting();
+    }
+
+    /// Registers a callback to invoke with the cycle of threads/locks
+    /// whenever a deadlock is detected, in place of the default panic.
+    pub fn on_deadlock(&self, callback: impl Fn(&[DeadlockParticipant]) + Send + Sync + 'static) {
+        self.write_lock().callback = Some(Arc::new(callback));
+    }
+
+    /// Every cycle detected so far, in detection order. Mainly useful for
+    /// tests that want to assert directly on what was found instead of
+    /// registering a callback via [`LockManager::on_deadlock`].
+    pub fn detected_cycles(&self) -> Vec<Vec<DeadlockParticipant>> {
+        self.write_lock().detected.clone()
+    }
+
+    fn report_deadlock(&self, cycle: Vec<DeadlockParticipant>) {
+        let callback = {
+            let mut state = self.write_lock();
+            state.detected.push(cycle.clone());
+            state.callback.clone()
+        };
+        match callback {
+            Some(callback) => callback(&cycle),
+            None => {
+                let mut message = String::from("deadlock detected:\n");
+                for participant in &cycle {
+                    message.push_str(&format!(
+                        "  thread {:?} waiting on lock #{}\n",
+                        participant.thread, participant.lock
+                    ));
+                }
+                panic!("{message}");
+            }
+        }
+    }
+}
+
+impl Default for LockManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Who currently holds a given lock: nobody, one or more readers, or a
+/// single exclusive writer.
+enum Holder {
+    Free,
+    Shared(Vec<Acquisition>),
+    Exclusive(Acquisition),
+}
+
+/// A single thread's hold on a lock, captured at the moment it acquired it.
+struct Acquisition {
+    thread: ThreadId,
+    #[cfg(feature = "backtrace")]
+    backtrace: Arc<std::backtrace::Backtrace>,
+}
+
+impl Acquisition {
+    fn new() -> Self {
+        Acquisition {
+            thread: std::thread::current().id(),
+            #[cfg(feature = "backtrace")]
+            backtrace: Arc::new(std::backtrace::Backtrace::force_capture()),
+        }
+    }
+}
+
+/// Bookkeeping the manager keeps for a single instrumented lock, or for a
+/// `Condvar`'s own key.
+pub(crate) struct LockRepresentation {
+    holder: Holder,
+    /// `Condvar` keys never have a holder; `walk` treats reaching one as a
+    /// dead end it should report immediately rather than recurse through.
+    is_condvar: bool,
+}
+
+impl LockRepresentation {
+    fn new() -> Self {
+        LockRepresentation {
+            holder: Holder::Free,
+            is_condvar: false,
+        }
+    }
+
+    fn new_condvar() -> Self {
+        LockRepresentation {
+            holder: Holder::Free,
+            is_condvar: true,
+        }
+    }
+
+    /// Attempts to take exclusive ownership, returning `false` without
+    /// blocking if it is already held by a reader or a writer.
+    pub(crate) fn try_write_lock(&mut self) -> bool {
+        match self.holder {
+            Holder::Free => {
+                self.holder = Holder::Exclusive(Acquisition::new());
+                true
+            }
+            Holder::Shared(_) | Holder::Exclusive(_) => false,
+        }
+    }
+
+    /// Attempts to take shared ownership, returning `false` without
+    /// blocking if it is already held by a writer.
+    pub(crate) fn try_read_lock(&mut self) -> bool {
+        match &mut self.holder {
+            Holder::Free => {
+                self.holder = Holder::Shared(vec![Acquisition::new()]);
+                true
+            }
+            Holder::Shared(readers) => {
+                readers.push(Acquisition::new());
+                true
+            }
+            Holder::Exclusive(_) => false,
+        }
+    }
+
+    pub(crate) fn unlock_write(&mut self) {
+        self.holder = Holder::Free;
+    }
+
+    pub(crate) fn unlock_read(&mut self) {
+        let id = std::thread::current().id();
+        if let Holder::Shared(readers) = &mut self.holder {
+            readers.retain(|reader| reader.thread != id);
+            if readers.is_empty() {
+                self.holder = Holder::Free;
+            }
+        }
+    }
+
+    fn holder_threads(&self) -> Vec<ThreadId> {
+        match &self.holder {
+            Holder::Free => Vec::new(),
+            Holder::Shared(readers) => readers.iter().map(|a| a.thread).collect(),
+            Holder::Exclusive(writer) => vec![writer.thread],
+        }
+    }
+
+    #[cfg(feature = "backtrace")]
+    fn acquisition_backtrace(&self, thread: ThreadId) -> Option<Arc<std::backtrace::Backtrace>> {
+        let acquisition = match &self.holder {
+            Holder::Free => None,
+            Holder::Shared(readers) => readers.iter().find(|a| a.thread == thread),
+            Holder::Exclusive(writer) => (writer.thread == thread).then_some(writer),
+        };
+        acquisition.map(|a| a.backtrace.clone())
+    }
+}
+
+impl ManagerState {
+    /// Records that `thread` is now blocked trying to acquire `lock`, and
+    /// searches the wait-for graph for a cycle rooted at `thread`. Returns
+    /// the cycle, in wait order, if one was found.
+    fn begin_wait(&mut self, thread: ThreadId, lock: usize) -> Option<Vec<DeadlockParticipant>> {
+        self.waiting_on.insert(thread, lock);
+
+        let mut path = Vec::new();
+        let mut seen = HashSet::new();
+        if self.walk(thread, thread, &mut path, &mut seen) {
+            Some(path)
+        } else {
+            None
+        }
+    }
+
+    /// Depth-first search of the wait-for graph: `current` waits on a lock,
+    /// which is held by one or more threads; recurse into each holder in
+    /// turn looking for a path back to `start`.
+    fn walk(
+        &self,
+        start: ThreadId,
+        current: ThreadId,
+        path: &mut Vec<DeadlockParticipant>,
+        seen: &mut HashSet<ThreadId>,
+    ) -> bool {
+        let Some(&lock) = self.waiting_on.get(&current) else {
+            return false;
+        };
+        if !seen.insert(current) {
+            return false;
+        }
+        let Some(representation) = self.locks.get(&lock) else {
+            return false;
+        };
+        if representation.is_condvar {
+            // Only a dead end when reached *transitively*: a lone wait with
+            // nothing depending on it yet (current == start, the thread
+            // that just parked) is not a deadlock.
+            if current == start {
+                return false;
+            }
+            path.push(DeadlockParticipant {
+                thread: current,
+                lock,
+                #[cfg(feature = "backtrace")]
+                acquired_at: None,
+            });
+            return true;
+        }
+        for holder in representation.holder_threads() {
+            path.push(DeadlockParticipant {
+                thread: current,
+                lock,
+                #[cfg(feature = "backtrace")]
+                acquired_at: representation.acquisition_backtrace(holder),
+            });
+            if holder == start || self.walk(start, holder, path, seen) {
+                return true;
+            }
+            path.pop();
+        }
+        false
+    }
+}
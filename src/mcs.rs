@@ -0,0 +1,111 @@
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
+use std::thread::{self, Thread};
+
+/// How many times a waiter spins on its own node before falling back to
+/// `thread::park`. A short spin avoids the cost of a park/unpark round
+/// trip for hand-offs that resolve almost immediately, without starving
+/// the lock holder of CPU time under oversubscription the way spinning
+/// for the whole wait would.
+const SPIN_LIMIT: u32 = 100;
+
+/// A thread's place in an `McsQueue`: a flag it waits on for its turn, a
+/// link to whoever enqueues behind it, and a handle so its predecessor can
+/// wake it if it has parked.
+pub(crate) struct Node {
+    locked: AtomicBool,
+    next: AtomicPtr<Node>,
+    thread: Thread,
+}
+
+impl Node {
+    fn new() -> Self {
+        Node {
+            locked: AtomicBool::new(true),
+            next: AtomicPtr::new(ptr::null_mut()),
+            thread: thread::current(),
+        }
+    }
+}
+
+/// An opaque handle to a queued [`Node`], returned by [`McsQueue::acquire`]
+/// and handed back to [`McsQueue::release`]. It is just a pointer, but
+/// raw pointers aren't `Send`/`Sync` by default, and this one is only ever
+/// dereferenced through the atomics inside `Node` and its `thread` handle,
+/// which is never written after construction, so shipping it to
+/// another thread (or sharing it) is sound.
+pub(crate) struct Ticket(*mut Node);
+
+unsafe impl Send for Ticket {}
+unsafe impl Sync for Ticket {}
+
+/// A fair, FIFO wait queue after Mellor-Crummey and Scott: each waiter
+/// spins only on a flag in its own node rather than on shared state, so
+/// contention doesn't scale with the number of waiters, and whoever
+/// enqueues first is released first.
+pub(crate) struct McsQueue {
+    tail: AtomicPtr<Node>,
+}
+
+impl McsQueue {
+    pub(crate) fn new() -> Self {
+        McsQueue {
+            tail: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    /// Joins the queue and blocks until it is this thread's turn, i.e.
+    /// until every thread that enqueued before it has released.
+    pub(crate) fn acquire(&self) -> Ticket {
+        let node = Box::into_raw(Box::new(Node::new()));
+        let predecessor = self.tail.swap(node, Ordering::AcqRel);
+        if !predecessor.is_null() {
+            // Someone is ahead of us: publish ourselves as their successor,
+            // then wait for them to release us, spinning briefly before
+            // parking so we don't burn the holder's timeslice.
+            let predecessor = unsafe { &*predecessor };
+            predecessor.next.store(node, Ordering::Release);
+            let ours = unsafe { &*node };
+            let mut spins = 0;
+            while ours.locked.load(Ordering::Acquire) {
+                if spins < SPIN_LIMIT {
+                    std::hint::spin_loop();
+                    spins += 1;
+                } else {
+                    thread::park();
+                }
+            }
+        }
+        Ticket(node)
+    }
+
+    /// Releases the queue, handing it off to whoever enqueued next, if
+    /// anyone has yet, and frees this thread's node.
+    pub(crate) fn release(&self, ticket: Ticket) {
+        let node = ticket.0;
+        let node_ref = unsafe { &*node };
+        if node_ref.next.load(Ordering::Acquire).is_null() {
+            if self
+                .tail
+                .compare_exchange(node, ptr::null_mut(), Ordering::AcqRel, Ordering::Acquire)
+                .is_ok()
+            {
+                unsafe { drop(Box::from_raw(node)) };
+                return;
+            }
+            // A successor is mid-enqueue but hasn't published itself yet.
+            while node_ref.next.load(Ordering::Acquire).is_null() {
+                std::hint::spin_loop();
+            }
+        }
+        let next = node_ref.next.load(Ordering::Acquire);
+        let next_ref = unsafe { &*next };
+        let successor = next_ref.thread.clone();
+        next_ref.locked.store(false, Ordering::Release);
+        // Wakes the successor immediately if it already parked; otherwise
+        // stores a permit it'll consume the moment it calls `park`, so the
+        // hand-off can never be missed regardless of timing.
+        successor.unpark();
+        unsafe { drop(Box::from_raw(node)) };
+    }
+}
@@ -1,29 +1,43 @@
-use std::sync::{LockResult, PoisonError, TryLockError, TryLockResult};
 use std::cell::UnsafeCell;
-use std::time::{Instant, Duration};
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, LockResult, PoisonError, TryLockError, TryLockResult};
+
+use crate::lock_manager::LockManager;
+use crate::mcs::{McsQueue, Ticket};
+use crate::poison::Poison;
 
 /// An instrumented version of `std::sync::Mutex`
 pub struct Mutex<T: ?Sized> {
     key: usize,
-    poisoned: bool,
-    manager: std::sync::Arc<crate::lock_manager::LockManager>,
+    poisoned: AtomicBool,
+    manager: Arc<LockManager>,
+    queue: McsQueue,
     inner: UnsafeCell<T>,
 }
 
 impl<T> Mutex<T> {
     pub fn new(inner: T) -> Self {
-        let manager = crate::lock_manager::LockManager::get_global_manager();
+        Self::with_manager(LockManager::get_global_manager(), inner)
+    }
+
+    /// Creates a mutex registered with `manager` instead of the global
+    /// one, so it is only visible to that manager's deadlock analysis.
+    /// Useful for tests or subsystems that want to reason about their own
+    /// locks in isolation.
+    pub fn with_manager(manager: Arc<LockManager>, inner: T) -> Self {
         let key = manager.create_lock();
         Mutex {
             inner: UnsafeCell::new(inner),
-            poisoned: false,
+            poisoned: AtomicBool::new(false),
+            queue: McsQueue::new(),
             manager,
             key,
         }
     }
 
     pub fn into_inner(self) -> LockResult<T> {
-        if self.poisoned {
+        if self.poisoned.load(Ordering::Relaxed) {
             Err(PoisonError::new(self.inner.into_inner()))
         } else {
             Ok(self.inner.into_inner())
@@ -33,8 +47,8 @@ impl<T> Mutex<T> {
 
 impl<T: ?Sized> Mutex<T> {
     pub fn get_mut(&mut self) -> LockResult<&mut T> {
-        let reference = unsafe {&mut *self.inner.get()};
-        if self.poisoned {
+        let reference = unsafe { &mut *self.inner.get() };
+        if self.poisoned.load(Ordering::Relaxed) {
             Err(PoisonError::new(reference))
         } else {
             Ok(reference)
@@ -42,16 +56,24 @@ impl<T: ?Sized> Mutex<T> {
     }
 
     pub fn is_poisoned(&self) -> bool {
-        self.poisoned
+        self.poisoned.load(Ordering::Relaxed)
     }
 
-    pub fn try_lock(&self) -> TryLockResult<MutexGuard<T>> {
-        let mut guard = self.manager.write_lock();
-        let representation = guard.locks.get_mut(&self.key).unwrap();
+    fn guard(&self, ticket: Option<Ticket>) -> MutexGuard<'_, T> {
+        MutexGuard {
+            inner: self,
+            poison: Poison::new(),
+            ticket,
+            _not_send: PhantomData,
+        }
+    }
+
+    pub fn try_lock(&self) -> TryLockResult<MutexGuard<'_, T>> {
+        let mut manager_guard = self.manager.write_lock();
+        let representation = manager_guard.locks.get_mut(&self.key).unwrap();
         if representation.try_write_lock() {
-            let returned_guard = MutexGuard {
-                inner: unsafe { &mut *(self as *const _ as *mut _) },
-            };
+            drop(manager_guard);
+            let returned_guard = self.guard(None);
             if self.is_poisoned() {
                 Err(TryLockError::Poisoned(PoisonError::new(returned_guard)))
             } else {
@@ -62,53 +84,79 @@ impl<T: ?Sized> Mutex<T> {
         }
     }
 
-    pub fn lock(&self) -> LockResult<MutexGuard<T>> {
-        let timeout = Duration::from_secs(1);
-        let start = Instant::now();
-
+    pub fn lock(&self) -> LockResult<MutexGuard<'_, T>> {
+        self.manager.wait_for(self.key);
+        // Join the fair FIFO queue: once we're released, every thread that
+        // was waiting before us has already had its turn.
+        let ticket = self.queue.acquire();
         loop {
-            let mut guard = self.manager.write_lock();
-            let representation = guard.locks.get_mut(&self.key).unwrap();
+            let mut manager_guard = self.manager.write_lock();
+            let representation = manager_guard.locks.get_mut(&self.key).unwrap();
             if representation.try_write_lock() {
-                let returned_guard = MutexGuard {
-                    inner: unsafe { &mut *(self as *const _ as *mut _) },
-                };
-                if self.is_poisoned() {
-                    return Err(PoisonError::new(returned_guard));
+                drop(manager_guard);
+                self.manager.stop_waiting();
+                let returned_guard = self.guard(Some(ticket));
+                return if self.is_poisoned() {
+                    Err(PoisonError::new(returned_guard))
                 } else {
-                    return Ok(returned_guard);
-                }
-            } else if Instant::now().duration_since(start) > timeout {
-                representation.subscribe_write();
-                guard.analyse();
-                std::thread::yield_now();
+                    Ok(returned_guard)
+                };
             }
+            drop(manager_guard);
+            std::thread::yield_now();
         }
     }
 }
 
 pub struct MutexGuard<'l, T: ?Sized> {
-    inner: &'l mut Mutex<T>,
+    inner: &'l Mutex<T>,
+    poison: Poison,
+    // Held until we unlock, so the next queued thread is only released
+    // once the lock it is waiting for is actually free.
+    ticket: Option<Ticket>,
+    // This marker suppresses the auto `Send` impl, so the guard is always
+    // dropped on the thread that created it, matching `std::sync::MutexGuard`.
+    _not_send: PhantomData<*const ()>,
+}
+impl<'l, T: ?Sized> MutexGuard<'l, T> {
+    /// Returns the mutex this guard was produced from, without otherwise
+    /// affecting its lock state. Used by [`crate::Condvar`] to reacquire
+    /// the mutex after a wait.
+    pub(crate) fn mutex(&self) -> &'l Mutex<T> {
+        self.inner
+    }
 }
-impl<'l, T> std::ops::Deref for MutexGuard<'l, T> {
+impl<'l, T: ?Sized> std::ops::Deref for MutexGuard<'l, T> {
     type Target = T;
-    fn deref(&self) -> &<Self as std::ops::Deref>::Target {
-        unsafe {&*self.inner.inner.get()}
+    fn deref(&self) -> &T {
+        unsafe { &*self.inner.inner.get() }
     }
 }
-impl<'l, T> std::ops::DerefMut for MutexGuard<'l, T> {
-    fn deref_mut(&mut self) -> &mut <Self as std::ops::Deref>::Target {
-        unsafe {&mut *self.inner.inner.get()}
+impl<'l, T: ?Sized> std::ops::DerefMut for MutexGuard<'l, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.inner.inner.get() }
     }
 }
 impl<'l, T: ?Sized> Drop for MutexGuard<'l, T> {
     fn drop(&mut self) {
-        let mut guard = self.inner.manager.write_lock();
-        guard.locks.get_mut(&self.inner.key).unwrap().unlock();
-        if std::thread::panicking() {
-            self.inner.poisoned = true;
+        self.poison.done(&self.inner.poisoned);
+        let mut manager_guard = self.inner.manager.write_lock();
+        manager_guard
+            .locks
+            .get_mut(&self.inner.key)
+            .unwrap()
+            .unlock_write();
+        drop(manager_guard);
+        if let Some(ticket) = self.ticket.take() {
+            self.inner.queue.release(ticket);
         }
     }
 }
 unsafe impl<T: Send> Send for Mutex<T> {}
-unsafe impl<T: Send> Sync for Mutex<T> {}
\ No newline at end of file
+unsafe impl<T: Send> Sync for Mutex<T> {}
+
+impl<T: Default> Default for Mutex<T> {
+    fn default() -> Self {
+        Mutex::new(T::default())
+    }
+}
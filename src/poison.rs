@@ -0,0 +1,23 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Captures whether the current thread was already panicking when a guard
+/// was created, so that `Drop` only poisons the lock for panics that
+/// actually happen while the guard is held. Without this, dropping a guard
+/// while unwinding from an unrelated panic would spuriously poison the lock.
+pub(crate) struct Poison {
+    acquired_panicking: bool,
+}
+
+impl Poison {
+    pub(crate) fn new() -> Self {
+        Poison {
+            acquired_panicking: std::thread::panicking(),
+        }
+    }
+
+    pub(crate) fn done(&self, poisoned: &AtomicBool) {
+        if !self.acquired_panicking && std::thread::panicking() {
+            poisoned.store(true, Ordering::Relaxed);
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use std::cell::UnsafeCell;
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, LockResult, PoisonError, TryLockError, TryLockResult};
+
+use crate::lock_manager::LockManager;
+use crate::mcs::{McsQueue, Ticket};
+use crate::poison::Poison;
+
+/// An instrumented version of `std::sync::RwLock`
+pub struct RwLock<T: ?Sized> {
+    key: usize,
+    poisoned: AtomicBool,
+    manager: Arc<LockManager>,
+    queue: McsQueue,
+    inner: UnsafeCell<T>,
+}
+
+impl<T> RwLock<T> {
+    pub fn new(inner: T) -> Self {
+        Self::with_manager(LockManager::get_global_manager(), inner)
+    }
+
+    /// Creates a rwlock registered with `manager` instead of the global
+    /// one, so it is only visible to that manager's deadlock analysis.
+    /// Useful for tests or subsystems that want to reason about their own
+    /// locks in isolation.
+    pub fn with_manager(manager: Arc<LockManager>, inner: T) -> Self {
+        let key = manager.create_lock();
+        RwLock {
+            inner: UnsafeCell::new(inner),
+            poisoned: AtomicBool::new(false),
+            queue: McsQueue::new(),
+            manager,
+            key,
+        }
+    }
+
+    pub fn into_inner(self) -> LockResult<T> {
+        if self.poisoned.load(Ordering::Relaxed) {
+            Err(PoisonError::new(self.inner.into_inner()))
+        } else {
+            Ok(self.inner.into_inner())
+        }
+    }
+}
+
+impl<T: ?Sized> RwLock<T> {
+    pub fn get_mut(&mut self) -> LockResult<&mut T> {
+        let reference = unsafe { &mut *self.inner.get() };
+        if self.poisoned.load(Ordering::Relaxed) {
+            Err(PoisonError::new(reference))
+        } else {
+            Ok(reference)
+        }
+    }
+
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned.load(Ordering::Relaxed)
+    }
+
+    fn read_guard(&self) -> RwLockReadGuard<'_, T> {
+        RwLockReadGuard {
+            inner: self,
+            poison: Poison::new(),
+            _not_send: PhantomData,
+        }
+    }
+
+    fn write_guard(&self, ticket: Option<Ticket>) -> RwLockWriteGuard<'_, T> {
+        RwLockWriteGuard {
+            inner: self,
+            poison: Poison::new(),
+            ticket,
+            _not_send: PhantomData,
+        }
+    }
+
+    pub fn try_read(&self) -> TryLockResult<RwLockReadGuard<'_, T>> {
+        let mut manager_guard = self.manager.write_lock();
+        let representation = manager_guard.locks.get_mut(&self.key).unwrap();
+        if representation.try_read_lock() {
+            drop(manager_guard);
+            let returned_guard = self.read_guard();
+            if self.is_poisoned() {
+                Err(TryLockError::Poisoned(PoisonError::new(returned_guard)))
+            } else {
+                Ok(returned_guard)
+            }
+        } else {
+            Err(TryLockError::WouldBlock)
+        }
+    }
+
+    pub fn try_write(&self) -> TryLockResult<RwLockWriteGuard<'_, T>> {
+        let mut manager_guard = self.manager.write_lock();
+        let representation = manager_guard.locks.get_mut(&self.key).unwrap();
+        if representation.try_write_lock() {
+            drop(manager_guard);
+            let returned_guard = self.write_guard(None);
+            if self.is_poisoned() {
+                Err(TryLockError::Poisoned(PoisonError::new(returned_guard)))
+            } else {
+                Ok(returned_guard)
+            }
+        } else {
+            Err(TryLockError::WouldBlock)
+        }
+    }
+
+    pub fn read(&self) -> LockResult<RwLockReadGuard<'_, T>> {
+        self.manager.wait_for(self.key);
+        // Join the fair FIFO queue so a steady stream of new readers can't
+        // starve a queued writer; the ticket is released as soon as we've
+        // registered as a reader, so other readers can still join us.
+        let ticket = self.queue.acquire();
+        loop {
+            let mut manager_guard = self.manager.write_lock();
+            let representation = manager_guard.locks.get_mut(&self.key).unwrap();
+            if representation.try_read_lock() {
+                drop(manager_guard);
+                self.manager.stop_waiting();
+                self.queue.release(ticket);
+                let returned_guard = self.read_guard();
+                return if self.is_poisoned() {
+                    Err(PoisonError::new(returned_guard))
+                } else {
+                    Ok(returned_guard)
+                };
+            }
+            drop(manager_guard);
+            std::thread::yield_now();
+        }
+    }
+
+    pub fn write(&self) -> LockResult<RwLockWriteGuard<'_, T>> {
+        self.manager.wait_for(self.key);
+        let ticket = self.queue.acquire();
+        loop {
+            let mut manager_guard = self.manager.write_lock();
+            let representation = manager_guard.locks.get_mut(&self.key).unwrap();
+            if representation.try_write_lock() {
+                drop(manager_guard);
+                self.manager.stop_waiting();
+                let returned_guard = self.write_guard(Some(ticket));
+                return if self.is_poisoned() {
+                    Err(PoisonError::new(returned_guard))
+                } else {
+                    Ok(returned_guard)
+                };
+            }
+            drop(manager_guard);
+            std::thread::yield_now();
+        }
+    }
+}
+
+pub struct RwLockReadGuard<'l, T: ?Sized> {
+    inner: &'l RwLock<T>,
+    poison: Poison,
+    // `unlock_read` identifies the releasing reader by `thread::current().id()`,
+    // so the guard must never be dropped on a different thread than the one
+    // that created it; this marker suppresses the auto `Send` impl, matching
+    // `std::sync::RwLockReadGuard`.
+    _not_send: PhantomData<*const ()>,
+}
+impl<'l, T: ?Sized> std::ops::Deref for RwLockReadGuard<'l, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { &*self.inner.inner.get() }
+    }
+}
+impl<'l, T: ?Sized> Drop for RwLockReadGuard<'l, T> {
+    fn drop(&mut self) {
+        self.poison.done(&self.inner.poisoned);
+        let mut manager_guard = self.inner.manager.write_lock();
+        manager_guard
+            .locks
+            .get_mut(&self.inner.key)
+            .unwrap()
+            .unlock_read();
+    }
+}
+
+pub struct RwLockWriteGuard<'l, T: ?Sized> {
+    inner: &'l RwLock<T>,
+    poison: Poison,
+    // Held until we unlock, so the next queued thread is only released
+    // once the lock it is waiting for is actually free.
+    ticket: Option<Ticket>,
+    // Released from whatever thread drops the guard; this marker suppresses
+    // the auto `Send` impl, matching `std::sync::RwLockWriteGuard`.
+    _not_send: PhantomData<*const ()>,
+}
+impl<'l, T: ?Sized> std::ops::Deref for RwLockWriteGuard<'l, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { &*self.inner.inner.get() }
+    }
+}
+impl<'l, T: ?Sized> std::ops::DerefMut for RwLockWriteGuard<'l, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.inner.inner.get() }
+    }
+}
+impl<'l, T: ?Sized> Drop for RwLockWriteGuard<'l, T> {
+    fn drop(&mut self) {
+        self.poison.done(&self.inner.poisoned);
+        let mut manager_guard = self.inner.manager.write_lock();
+        manager_guard
+            .locks
+            .get_mut(&self.inner.key)
+            .unwrap()
+            .unlock_write();
+        drop(manager_guard);
+        if let Some(ticket) = self.ticket.take() {
+            self.inner.queue.release(ticket);
+        }
+    }
+}
+
+unsafe impl<T: Send> Send for RwLock<T> {}
+unsafe impl<T: Send + Sync> Sync for RwLock<T> {}
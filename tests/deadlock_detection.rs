@@ -0,0 +1,181 @@
+//! End-to-end tests exercising deadlock detection across the public API:
+//! a plain two-mutex cycle, a reader/writer cycle, poisoning, and a thread
+//! parked on a `Condvar` while still holding a lock another thread needs.
+//!
+//! Each deadlock test builds its own `LockManager` with `LockManager::new()`
+//! so it can assert on `detected_cycles()` directly instead of sharing the
+//! process-wide manager with every other test; the lone-wait and poisoning
+//! tests use the default constructors and the global manager.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Barrier};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use deadlocks_debugger::{Condvar, LockManager, Mutex, RwLock};
+
+/// Polls `found` until it returns `true`, panicking if `timeout` elapses
+/// first. Deadlock detection happens on another thread, so tests can't
+/// just check it synchronously.
+fn wait_until(timeout: Duration, mut found: impl FnMut() -> bool) {
+    let deadline = Instant::now() + timeout;
+    while !found() {
+        assert!(Instant::now() < deadline, "condition was never met");
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+#[test]
+fn two_thread_mutex_cycle_is_detected() {
+    let manager = Arc::new(LockManager::new());
+    // Suppress the default panic-on-deadlock so the involved threads just
+    // stay blocked once the cycle is reported.
+    manager.on_deadlock(|_| {});
+
+    let a = Arc::new(Mutex::with_manager(manager.clone(), ()));
+    let b = Arc::new(Mutex::with_manager(manager.clone(), ()));
+    let barrier = Arc::new(Barrier::new(2));
+
+    {
+        let a = a.clone();
+        let b = b.clone();
+        let barrier = barrier.clone();
+        thread::spawn(move || {
+            let _guard_a = a.lock().unwrap();
+            barrier.wait();
+            let _guard_b = b.lock().unwrap();
+        });
+    }
+    {
+        let barrier = barrier.clone();
+        thread::spawn(move || {
+            let _guard_b = b.lock().unwrap();
+            barrier.wait();
+            let _guard_a = a.lock().unwrap();
+        });
+    }
+
+    wait_until(Duration::from_secs(5), || !manager.detected_cycles().is_empty());
+}
+
+#[test]
+fn reader_writer_cycle_is_detected() {
+    let manager = Arc::new(LockManager::new());
+    manager.on_deadlock(|_| {});
+
+    let x = Arc::new(RwLock::with_manager(manager.clone(), ()));
+    let y = Arc::new(RwLock::with_manager(manager.clone(), ()));
+    let barrier = Arc::new(Barrier::new(2));
+
+    {
+        // Holds a shared read on X, then wants exclusive access to Y.
+        let x = x.clone();
+        let y = y.clone();
+        let barrier = barrier.clone();
+        thread::spawn(move || {
+            let _reader = x.read().unwrap();
+            barrier.wait();
+            let _writer = y.write().unwrap();
+        });
+    }
+    {
+        // Holds exclusive Y, then wants exclusive access to X, which is
+        // incompatible with the other thread's read lock.
+        let barrier = barrier.clone();
+        thread::spawn(move || {
+            let _writer = y.write().unwrap();
+            barrier.wait();
+            let _writer = x.write().unwrap();
+        });
+    }
+
+    wait_until(Duration::from_secs(5), || !manager.detected_cycles().is_empty());
+}
+
+#[test]
+fn condvar_park_while_holding_lock_is_detected() {
+    let manager = Arc::new(LockManager::new());
+    manager.on_deadlock(|_| {});
+
+    let a = Arc::new(Mutex::with_manager(manager.clone(), ()));
+    let b = Arc::new(Mutex::with_manager(manager.clone(), 0));
+    let condvar = Arc::new(Condvar::with_manager(manager.clone()));
+    let about_to_park = Arc::new(AtomicBool::new(false));
+
+    {
+        // Holds A, then holds B and parks on the condvar without ever
+        // being notified, all while still holding A.
+        let a = a.clone();
+        let b = b.clone();
+        let condvar = condvar.clone();
+        let about_to_park = about_to_park.clone();
+        thread::spawn(move || {
+            let _guard_a = a.lock().unwrap();
+            let guard_b = b.lock().unwrap();
+            about_to_park.store(true, Ordering::SeqCst);
+            let _guard_b = condvar.wait(guard_b).unwrap();
+        });
+    }
+    {
+        // Waits for the first thread to have acquired A before trying to
+        // acquire it itself, so the wait-for edge exists when it blocks.
+        let a = a.clone();
+        let about_to_park = about_to_park.clone();
+        thread::spawn(move || {
+            while !about_to_park.load(Ordering::SeqCst) {
+                thread::yield_now();
+            }
+            thread::sleep(Duration::from_millis(20));
+            let _guard_a = a.lock().unwrap();
+        });
+    }
+
+    wait_until(Duration::from_secs(5), || !manager.detected_cycles().is_empty());
+}
+
+#[test]
+fn condvar_lone_wait_is_not_a_false_deadlock() {
+    // A plain, uncontended wait/notify: nothing else depends on the
+    // waiting thread's locks, so it must never be reported as a deadlock
+    // (and, with the default callback, must not panic).
+    let mutex = Arc::new(Mutex::new(false));
+    let condvar = Arc::new(Condvar::new());
+    let (done_tx, done_rx) = std::sync::mpsc::channel();
+
+    {
+        let mutex = mutex.clone();
+        let condvar = condvar.clone();
+        thread::spawn(move || {
+            let mut ready = mutex.lock().unwrap();
+            while !*ready {
+                ready = condvar.wait(ready).unwrap();
+            }
+            done_tx.send(()).unwrap();
+        });
+    }
+
+    *mutex.lock().unwrap() = true;
+    condvar.notify_one();
+
+    done_rx
+        .recv_timeout(Duration::from_secs(5))
+        .expect("waiter never woke up");
+}
+
+#[test]
+fn panic_while_holding_mutex_poisons_it() {
+    let mutex = Arc::new(Mutex::new(0));
+
+    {
+        let mutex = mutex.clone();
+        let handle = thread::spawn(move || {
+            let mut guard = mutex.lock().unwrap();
+            *guard += 1;
+            panic!("simulated failure while holding the lock");
+        });
+        assert!(handle.join().is_err());
+    }
+
+    assert!(mutex.is_poisoned());
+    assert!(mutex.lock().is_err());
+}